@@ -0,0 +1,165 @@
+use std::path::Path;
+use std::process::Command;
+
+/// A placeholder that can appear inside an `-x`/`--exec` argument template.
+enum Placeholder {
+    /// Literal text with no special meaning.
+    Text(String),
+
+    /// `{}`: the full path of the match.
+    Path,
+
+    /// `{.}`: the full path of the match, without its extension.
+    PathWithoutExt,
+
+    /// `{/}`: the basename of the match.
+    Basename,
+
+    /// `{/.}`: the basename of the match, without its extension.
+    BasenameWithoutExt,
+
+    /// `{//}`: the parent directory of the match.
+    Parent
+}
+
+/// A single argument of the `-x` command template, split into literal text
+/// and placeholders so that it can be re-assembled for every match.
+struct ArgumentTemplate(Vec<Placeholder>);
+
+impl ArgumentTemplate {
+    /// Parse a raw command-line token into a sequence of placeholders.
+    fn parse(arg: &str) -> ArgumentTemplate {
+        let mut placeholders = Vec::new();
+        let mut rest = arg;
+
+        const MARKERS: &[&str] = &["{//}", "{/.}", "{/}", "{.}", "{}"];
+
+        while !rest.is_empty() {
+            let found = MARKERS.iter()
+                                .filter_map(|&marker| rest.find(marker).map(|idx| (idx, marker)))
+                                .min_by_key(|&(idx, _)| idx);
+
+            match found {
+                Some((idx, marker)) => {
+                    if idx > 0 {
+                        placeholders.push(Placeholder::Text(rest[..idx].to_owned()));
+                    }
+                    placeholders.push(match marker {
+                        "{//}" => Placeholder::Parent,
+                        "{/.}" => Placeholder::BasenameWithoutExt,
+                        "{/}"  => Placeholder::Basename,
+                        "{.}"  => Placeholder::PathWithoutExt,
+                        _      => Placeholder::Path
+                    });
+                    rest = &rest[idx + marker.len()..];
+                }
+                None => {
+                    placeholders.push(Placeholder::Text(rest.to_owned()));
+                    break;
+                }
+            }
+        }
+
+        ArgumentTemplate(placeholders)
+    }
+
+    /// Substitute every placeholder with the relevant part of `path`.
+    fn generate(&self, path: &Path) -> String {
+        let path_str = path.to_string_lossy().into_owned();
+        let without_ext = path.with_extension("").to_string_lossy().into_owned();
+        let basename = path.file_name()
+                           .map(|s| s.to_string_lossy().into_owned())
+                           .unwrap_or_default();
+        let basename_without_ext = path.file_stem()
+                                       .map(|s| s.to_string_lossy().into_owned())
+                                       .unwrap_or_default();
+        let parent = path.parent()
+                         .map(|s| s.to_string_lossy().into_owned())
+                         .unwrap_or_default();
+
+        self.0.iter().map(|p| match *p {
+            Placeholder::Text(ref t)        => t.clone(),
+            Placeholder::Path               => path_str.clone(),
+            Placeholder::PathWithoutExt     => without_ext.clone(),
+            Placeholder::Basename           => basename.clone(),
+            Placeholder::BasenameWithoutExt => basename_without_ext.clone(),
+            Placeholder::Parent             => parent.clone()
+        }).collect()
+    }
+}
+
+/// A `-x`/`--exec` command template: the program name followed by its
+/// arguments, each of which may reference the matched path.
+pub struct CommandTemplate {
+    program: ArgumentTemplate,
+    args: Vec<ArgumentTemplate>
+}
+
+impl CommandTemplate {
+    /// Build a template from the raw tokens that followed `-x` on the
+    /// command line. Returns `None` if no tokens were given.
+    pub fn new(tokens: &[String]) -> Option<CommandTemplate> {
+        let mut iter = tokens.iter();
+        let program = ArgumentTemplate::parse(iter.next()?);
+        let args = iter.map(|a| ArgumentTemplate::parse(a)).collect();
+
+        Some(CommandTemplate { program: program, args: args })
+    }
+
+    /// Produce a ready-to-run `Command` with all placeholders substituted
+    /// for the given matched path.
+    pub fn generate(&self, path: &Path) -> Command {
+        let mut cmd = Command::new(self.program.generate(path));
+        for arg in &self.args {
+            cmd.arg(arg.generate(path));
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_full_path_and_basename() {
+        let path = Path::new("/tmp/dir/photo.jpg");
+
+        assert_eq!(ArgumentTemplate::parse("{}").generate(path), "/tmp/dir/photo.jpg");
+        assert_eq!(ArgumentTemplate::parse("{/}").generate(path), "photo.jpg");
+        assert_eq!(ArgumentTemplate::parse("{//}").generate(path), "/tmp/dir");
+    }
+
+    #[test]
+    fn substitutes_without_extension_placeholders_inside_literal_text() {
+        let path = Path::new("/tmp/dir/photo.jpg");
+
+        assert_eq!(ArgumentTemplate::parse("{.}.png").generate(path), "/tmp/dir/photo.png");
+        assert_eq!(ArgumentTemplate::parse("{/.}.png").generate(path), "photo.png");
+    }
+
+    #[test]
+    fn handles_a_path_without_a_parent_or_extension() {
+        let path = Path::new("photo");
+
+        assert_eq!(ArgumentTemplate::parse("{//}").generate(path), "");
+        assert_eq!(ArgumentTemplate::parse("{.}").generate(path), "photo");
+        assert_eq!(ArgumentTemplate::parse("{/.}").generate(path), "photo");
+    }
+
+    #[test]
+    fn builds_a_command_from_a_template() {
+        let tokens = vec!["convert".to_owned(), "{}".to_owned(), "{.}.png".to_owned()];
+        let template = CommandTemplate::new(&tokens).expect("template should parse");
+        let cmd = template.generate(Path::new("/tmp/photo.jpg"));
+
+        assert_eq!(cmd.get_program(), "convert");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, &["/tmp/photo.jpg", "/tmp/photo.png"]);
+    }
+
+    #[test]
+    fn returns_none_for_an_empty_template() {
+        assert!(CommandTemplate::new(&[]).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::env;
+
+use ansi_term::{Colour, Style};
+
+/// A parsed representation of the `LS_COLORS` environment variable: styles
+/// keyed by file-type code (`di`, `ln`, `ex`, ...) and by file extension.
+#[derive(Clone)]
+pub struct LsColors {
+    file_types: HashMap<String, Style>,
+    extensions: HashMap<String, Style>
+}
+
+impl LsColors {
+    /// Parse `LS_COLORS` from the environment, if it is set.
+    pub fn from_env() -> Option<LsColors> {
+        env::var("LS_COLORS").ok().map(|s| LsColors::from_string(&s))
+    }
+
+    fn from_string(input: &str) -> LsColors {
+        let mut file_types = HashMap::new();
+        let mut extensions = HashMap::new();
+
+        for entry in input.split(':') {
+            let mut parts = entry.splitn(2, '=');
+            let key = match parts.next() {
+                Some(k) if !k.is_empty() => k,
+                _                        => continue
+            };
+            let value = match parts.next() {
+                Some(v) => v,
+                None    => continue
+            };
+            let style = match parse_style(value) {
+                Some(s) => s,
+                None    => continue
+            };
+
+            if key.starts_with("*.") {
+                extensions.insert(key[2..].to_lowercase(), style);
+            } else {
+                file_types.insert(key.to_owned(), style);
+            }
+        }
+
+        LsColors { file_types: file_types, extensions: extensions }
+    }
+
+    pub fn directory_style(&self) -> Option<Style> {
+        self.file_types.get("di").cloned()
+    }
+
+    pub fn symlink_style(&self) -> Option<Style> {
+        self.file_types.get("ln").cloned()
+    }
+
+    pub fn executable_style(&self) -> Option<Style> {
+        self.file_types.get("ex").cloned()
+    }
+
+    pub fn extension_style(&self, ext: &str) -> Option<Style> {
+        self.extensions.get(&ext.to_lowercase()).cloned()
+    }
+}
+
+/// Parse a `;`-separated list of SGR codes (e.g. `01;34`) into a `Style`.
+fn parse_style(codes: &str) -> Option<Style> {
+    let mut style = Style::new();
+    let mut found = false;
+
+    for code in codes.split(';') {
+        let n: u8 = match code.parse() {
+            Ok(n)  => n,
+            Err(_) => continue
+        };
+        found = true;
+
+        style = match n {
+            1  => style.bold(),
+            4  => style.underline(),
+            30 => style.fg(Colour::Black),
+            31 => style.fg(Colour::Red),
+            32 => style.fg(Colour::Green),
+            33 => style.fg(Colour::Yellow),
+            34 => style.fg(Colour::Blue),
+            35 => style.fg(Colour::Purple),
+            36 => style.fg(Colour::Cyan),
+            37 => style.fg(Colour::White),
+            _  => style
+        };
+    }
+
+    if found { Some(style) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_file_type_and_extension_entries() {
+        let colors = LsColors::from_string("di=01;34:ln=35:*.jpg=33");
+
+        assert_eq!(colors.directory_style(), Some(Style::new().bold().fg(Colour::Blue)));
+        assert_eq!(colors.symlink_style(), Some(Style::new().fg(Colour::Purple)));
+        assert_eq!(colors.extension_style("jpg"), Some(Style::new().fg(Colour::Yellow)));
+    }
+
+    #[test]
+    fn extension_lookup_is_case_insensitive() {
+        let colors = LsColors::from_string("*.JPG=33");
+
+        assert_eq!(colors.extension_style("jpg"), Some(Style::new().fg(Colour::Yellow)));
+        assert_eq!(colors.extension_style("JPG"), Some(Style::new().fg(Colour::Yellow)));
+    }
+
+    #[test]
+    fn ignores_malformed_entries() {
+        let colors = LsColors::from_string("di:no-separator-here:=34:ln=nope:ex=;;");
+
+        assert_eq!(colors.directory_style(), None);
+        assert_eq!(colors.symlink_style(), None);
+        assert_eq!(colors.executable_style(), None);
+    }
+
+    #[test]
+    fn unknown_sgr_codes_are_ignored_but_dont_drop_known_ones() {
+        let colors = LsColors::from_string("ex=99;32");
+
+        assert_eq!(colors.executable_style(), Some(Style::new().fg(Colour::Green)));
+    }
+}
@@ -1,81 +1,242 @@
-extern crate walkdir;
+extern crate ignore;
 extern crate regex;
 extern crate getopts;
 extern crate ansi_term;
+extern crate num_cpus;
+extern crate atty;
+
+mod exec;
+mod lscolors;
 
 use std::env;
 use std::error::Error;
 use std::ffi::OsStr;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::Path;
 use std::process;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
 
-use walkdir::{WalkDir, DirEntry, WalkDirIterator};
+use ignore::{WalkBuilder, WalkState, DirEntry};
 use regex::{Regex, RegexBuilder};
 use getopts::Options;
-use ansi_term::Colour;
+use ansi_term::{Colour, Style};
+
+use exec::CommandTemplate;
+use lscolors::LsColors;
+
+/// The `--type`/`-t` filter: restricts matches to a single kind of entry.
+#[derive(Clone, Copy, PartialEq)]
+enum FileType {
+    Regular,
+    Directory,
+    SymLink,
+    Executable
+}
+
+/// Whether printed paths are shown relative to their search root, or as
+/// absolute paths.
+#[derive(Clone, Copy, PartialEq)]
+enum PathDisplay {
+    Relative,
+    Absolute
+}
 
+#[derive(Clone)]
 struct FdOptions {
     case_sensitive: bool,
     search_full_path: bool,
     search_hidden: bool,
+    read_ignore: bool,
     follow_links: bool,
-    colored: bool
+    colored: bool,
+    threads: usize,
+    command: Option<Arc<CommandTemplate>>,
+    file_type: Option<FileType>,
+    ls_colors: Option<LsColors>,
+    path_display: PathDisplay,
+    max_depth: Option<usize>,
+    null_separator: bool
 }
 
-/// Print a search result to the console.
-fn print_entry(entry: &DirEntry, path_str: &str, config: &FdOptions) {
-    if config.colored {
-        let style = match entry {
-            e if e.path_is_symbolic_link() => Colour::Purple,
-            e if e.path().is_dir()         => Colour::Cyan,
-            _                              => Colour::White
-        };
-        println!("{}", style.paint(path_str));
-    } else {
-        println!("{}", path_str);
+/// Check whether `entry` matches the requested `--type` filter.
+fn matches_file_type(entry: &DirEntry, file_type: FileType) -> bool {
+    match file_type {
+        FileType::Directory => entry.file_type().map_or(false, |t| t.is_dir()),
+        FileType::SymLink   => entry.file_type().map_or(false, |t| t.is_symlink()),
+        FileType::Regular   => entry.file_type().map_or(false, |t| t.is_file()),
+        FileType::Executable => entry.file_type().map_or(false, |t| t.is_file()) &&
+                                 is_executable(entry)
     }
 }
 
-/// Check if filename of entry starts with a dot.
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry.file_name()
-         .to_str()
-         .map(|s| s.starts_with("."))
+#[cfg(unix)]
+fn is_executable(entry: &DirEntry) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    entry.metadata()
+         .map(|m| m.permissions().mode() & 0o111 != 0)
          .unwrap_or(false)
 }
 
+#[cfg(not(unix))]
+fn is_executable(_entry: &DirEntry) -> bool {
+    false
+}
+
+/// Determine the style to paint a single path component with, based on
+/// whether it is the final component of the match (so it can be a file)
+/// or an intermediate directory component.
+fn component_style(entry: &DirEntry, component: &str, is_last: bool,
+                    ls_colors: &LsColors) -> Option<Style> {
+    if !is_last {
+        return ls_colors.directory_style();
+    }
+
+    if entry.file_type().map_or(false, |t| t.is_symlink()) {
+        ls_colors.symlink_style()
+    } else if entry.path().is_dir() {
+        ls_colors.directory_style()
+    } else if is_executable(entry) {
+        ls_colors.executable_style()
+    } else {
+        Path::new(component).extension()
+             .and_then(OsStr::to_str)
+             .and_then(|ext| ls_colors.extension_style(ext))
+    }
+}
+
+/// Colorize `path_str` component by component according to `ls_colors`.
+fn paint_with_ls_colors(entry: &DirEntry, path_str: &str, ls_colors: &LsColors) -> String {
+    let components: Vec<&str> = path_str.split('/').collect();
+    let last = components.len() - 1;
+
+    components.iter().enumerate().map(|(i, component)| {
+        match component_style(entry, component, i == last, ls_colors) {
+            Some(style) => style.paint(*component).to_string(),
+            None        => component.to_string()
+        }
+    }).collect::<Vec<_>>().join("/")
+}
+
+/// Print a search result to `out`, terminated by a newline or, if
+/// `--print0` was given, a NUL byte.
+fn print_entry<W: Write>(out: &mut W, entry: &DirEntry, path_str: &str, config: &FdOptions) {
+    let separator = if config.null_separator { "\0" } else { "\n" };
+
+    if !config.colored {
+        let _ = write!(out, "{}{}", path_str, separator);
+        return;
+    }
+
+    let result = match config.ls_colors {
+        Some(ref ls_colors) => write!(out, "{}{}", paint_with_ls_colors(entry, path_str, ls_colors), separator),
+        None => {
+            let style = match entry {
+                e if e.file_type().map_or(false, |t| t.is_symlink()) => Colour::Purple,
+                e if e.path().is_dir()                               => Colour::Cyan,
+                _                                                    => Colour::White
+            };
+            write!(out, "{}{}", style.paint(path_str), separator)
+        }
+    };
+    let _ = result;
+}
+
 /// Recursively scan the given root path and search for files / pathnames
-/// matching the pattern.
+/// matching the pattern, using a pool of worker threads that either feed
+/// matches to a single printer thread (so output never interleaves) or,
+/// when `-x`/`--exec` was given, run the command template directly.
 fn scan(root: &Path, pattern: &Regex, config: &FdOptions) {
-    let walker = WalkDir::new(root)
+    let walker = WalkBuilder::new(root)
+                     .hidden(!config.search_hidden)
+                     .ignore(config.read_ignore)
+                     .git_ignore(config.read_ignore)
+                     .git_global(config.read_ignore)
+                     .git_exclude(config.read_ignore)
                      .follow_links(config.follow_links)
-                     .into_iter()
-                     .filter_entry(|e| config.search_hidden || !is_hidden(e))
-                     .filter_map(|e| e.ok())
-                     .filter(|e| e.path() != root);
-
-    for entry in walker {
-        let path_rel = match entry.path().strip_prefix(root) {
-            Ok(p) => p,
-            Err(_) => continue
-        };
+                     .threads(config.threads)
+                     .max_depth(config.max_depth)
+                     .build_parallel();
+
+    let (tx, rx) = mpsc::channel::<(DirEntry, String)>();
+    let config = Arc::new(config.clone());
 
-        if let Some(path_str) = path_rel.to_str() {
-            let res =
-                if config.search_full_path {
-                    pattern.find(path_str)
-                } else {
-                    if !path_rel.is_file() { continue }
+    let printer = {
+        let config = config.clone();
+        thread::spawn(move || {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            for (entry, path_str) in rx {
+                print_entry(&mut handle, &entry, &path_str, &config);
+            }
+        })
+    };
 
-                    path_rel.file_name()
-                            .and_then(OsStr::to_str)
-                            .and_then(|s| pattern.find(s))
-                };
+    let root_buf = root.to_path_buf();
+    let root_abs = root.canonicalize().unwrap_or_else(|_| root_buf.clone());
+    let pattern = pattern.clone();
 
-            res.map(|_| print_entry(&entry, path_str, &config));
-        }
-    }
+    walker.run(|| {
+        let tx = tx.clone();
+        let root_buf = root_buf.clone();
+        let root_abs = root_abs.clone();
+        let pattern = pattern.clone();
+        let config = config.clone();
+
+        Box::new(move |result| {
+            let entry = match result {
+                Ok(e)  => e,
+                Err(_) => return WalkState::Continue
+            };
+
+            if entry.path() == root_buf { return WalkState::Continue }
+
+            let path_rel = match entry.path().strip_prefix(&root_buf) {
+                Ok(p) => p,
+                Err(_) => return WalkState::Continue
+            };
+
+            match config.file_type {
+                Some(ft) => if !matches_file_type(&entry, ft) { return WalkState::Continue },
+                None     => if !config.search_full_path && !entry.path().is_file() {
+                                return WalkState::Continue
+                            }
+            }
+
+            if let Some(path_str) = path_rel.to_str() {
+                let res =
+                    if config.search_full_path {
+                        pattern.find(path_str)
+                    } else {
+                        path_rel.file_name()
+                                .and_then(OsStr::to_str)
+                                .and_then(|s| pattern.find(s))
+                    };
+
+                if res.is_some() {
+                    match config.command {
+                        Some(ref cmd) => { let _ = cmd.generate(entry.path()).status(); }
+                        None          => {
+                            let display_str = match config.path_display {
+                                PathDisplay::Relative =>
+                                    path_str.to_owned(),
+                                PathDisplay::Absolute =>
+                                    root_abs.join(path_rel).to_string_lossy().into_owned()
+                            };
+                            let _ = tx.send((entry.clone(), display_str));
+                        }
+                    }
+                }
+            }
+
+            WalkState::Continue
+        })
+    });
+
+    drop(tx);
+    printer.join().expect("Printer thread panicked");
 }
 
 /// Print error message to stderr and exit with status `1`.
@@ -85,8 +246,21 @@ fn error(message: &str) -> ! {
     process::exit(1);
 }
 
+/// Split `-x`/`--exec` and everything after it off of `args`, since those
+/// trailing tokens form the command template and aren't `fd`'s own flags.
+fn extract_command(args: &[String]) -> (Vec<String>, Option<CommandTemplate>) {
+    match args.iter().position(|a| a == "-x" || a == "--exec") {
+        Some(pos) => {
+            let command = CommandTemplate::new(&args[pos + 1..]);
+            (args[..pos].to_vec(), command)
+        }
+        None => (args.to_vec(), None)
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let all_args: Vec<String> = env::args().collect();
+    let (args, command) = extract_command(&all_args[1..]);
 
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help message");
@@ -96,10 +270,21 @@ fn main() {
                       "search filenames only (default: full path)");
     opts.optflag("", "hidden",
                       "search hidden files/directories (default: off)");
+    opts.optflag("I", "no-ignore",
+                      "do not respect .(git)ignore files (default: off)");
     opts.optflag("F", "follow", "follow symlinks (default: off)");
     opts.optflag("n", "no-color", "do not colorize output");
+    opts.optopt("", "threads", "number of threads to use (default: number of CPUs)", "N");
+    opts.optflag("x", "exec",
+                      "execute a command for each search result (must be last argument)");
+    opts.optopt("t", "type",
+                     "filter by entry type: f(ile), d(irectory), l(ink), x(ecutable)", "TYPE");
+    opts.optflag("a", "absolute-path",
+                      "show absolute paths instead of paths relative to each search root");
+    opts.optopt("d", "max-depth", "set maximum search depth (default: unlimited)", "N");
+    opts.optflag("0", "print0", "separate results by the NUL character (for piping to xargs -0)");
 
-    let matches = match opts.parse(&args[1..]) {
+    let matches = match opts.parse(&args) {
         Ok(m)  => m,
         Err(e) => error(e.description())
     };
@@ -113,12 +298,22 @@ fn main() {
     let empty = String::new();
     let pattern = matches.free.get(0).unwrap_or(&empty);
 
-    let current_dir_buf = match env::current_dir() {
-        Ok(cd) => cd,
-        Err(_) => error("Could not get current directory!")
-    };
-    let current_dir = current_dir_buf.as_path();
-
+    let roots: Vec<_> =
+        if matches.free.len() > 1 {
+            matches.free[1..].iter().map(|p| {
+                let path = Path::new(p);
+                if !path.is_dir() {
+                    error(&format!("'{}' is not a directory", p));
+                }
+                path.to_path_buf()
+            }).collect()
+        } else {
+            let current_dir_buf = match env::current_dir() {
+                Ok(cd) => cd,
+                Err(_) => error("Could not get current directory!")
+            };
+            vec![current_dir_buf]
+        };
 
     let config = FdOptions {
         // The search will be case-sensitive if the command line flag is set or
@@ -127,14 +322,36 @@ fn main() {
                            pattern.chars().any(char::is_uppercase),
         search_full_path: !matches.opt_present("filename"),
         search_hidden:     matches.opt_present("hidden"),
-        colored:          !matches.opt_present("no-color"),
-        follow_links:      matches.opt_present("follow")
+        read_ignore:      !matches.opt_present("no-ignore"),
+        colored:          !matches.opt_present("no-color") && atty::is(atty::Stream::Stdout),
+        follow_links:      matches.opt_present("follow"),
+        threads:           matches.opt_str("threads")
+                                  .and_then(|n| n.parse().ok())
+                                  .unwrap_or_else(num_cpus::get),
+        command:           command.map(Arc::new),
+        file_type:         match matches.opt_str("type").as_ref().map(String::as_str) {
+                                Some("f") | Some("file")       => Some(FileType::Regular),
+                                Some("d") | Some("directory")  => Some(FileType::Directory),
+                                Some("l") | Some("link")       => Some(FileType::SymLink),
+                                Some("x") | Some("executable") => Some(FileType::Executable),
+                                Some(other)                    =>
+                                    error(&format!("Unrecognized file type '{}'", other)),
+                                None                            => None
+                            },
+        ls_colors:         LsColors::from_env(),
+        path_display:      if matches.opt_present("absolute-path") {
+                                PathDisplay::Absolute
+                            } else {
+                                PathDisplay::Relative
+                            },
+        max_depth:         matches.opt_str("max-depth").and_then(|n| n.parse().ok()),
+        null_separator:    matches.opt_present("print0")
     };
 
     match RegexBuilder::new(pattern)
               .case_insensitive(!config.case_sensitive)
               .build() {
-        Ok(re)   => scan(&current_dir, &re, &config),
+        Ok(re)   => for root in &roots { scan(root, &re, &config) },
         Err(err) => error(err.description())
     }
 }